@@ -0,0 +1,75 @@
+//! Self-contained proleptic Gregorian calendar math, used where we need to turn a Unix
+//! timestamp into calendar fields (or back) without pulling in a full timezone-aware
+//! date/time crate.
+
+pub(crate) struct Tm {
+    pub(crate) year: i64,
+    pub(crate) month: i64, // 1..=12
+    pub(crate) day: i64,   // 1..=31
+    pub(crate) hour: i64,
+    pub(crate) min: i64,
+    pub(crate) sec: i64,
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+const DAYS_IN_MONTH: [[i64; 12]; 2] = [
+    [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31],
+    [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31],
+];
+
+/// Converts a Unix timestamp (seconds since 1970-01-01T00:00:00Z) into UTC calendar fields.
+pub(crate) fn time_to_tm(ts: i64) -> Tm {
+    let mut days = ts.div_euclid(86400);
+    let mut secs_of_day = ts.rem_euclid(86400);
+
+    let hour = secs_of_day / 3600;
+    secs_of_day %= 3600;
+    let min = secs_of_day / 60;
+    let sec = secs_of_day % 60;
+
+    let mut year = 1970;
+    loop {
+        let year_days = if is_leap_year(year) { 366 } else { 365 };
+        if days >= year_days {
+            days -= year_days;
+            year += 1;
+        } else if days < 0 {
+            year -= 1;
+            days += if is_leap_year(year) { 366 } else { 365 };
+        } else {
+            break;
+        }
+    }
+
+    let months = &DAYS_IN_MONTH[is_leap_year(year) as usize];
+    let mut month = 0;
+    while days >= months[month] {
+        days -= months[month];
+        month += 1;
+    }
+
+    Tm {
+        year,
+        month: month as i64 + 1,
+        day: days + 1,
+        hour,
+        min,
+        sec,
+    }
+}
+
+/// Converts UTC calendar fields back into a Unix timestamp. Inverse of `time_to_tm`.
+pub(crate) fn tm_to_time(tm: &Tm) -> i64 {
+    let mut y = tm.year;
+    let mut m = tm.month;
+    if m <= 2 {
+        y -= 1;
+        m += 12;
+    }
+
+    let days = 365 * y + y / 4 - y / 100 + y / 400 + 3 * (m + 1) / 5 + 30 * m + tm.day - 719561;
+    days * 86400 + tm.hour * 3600 + tm.min * 60 + tm.sec
+}
@@ -2,40 +2,126 @@ use chrono::prelude::*;
 #[cfg(unix)]
 use nix::sys::time::{TimeVal, TimeValLike};
 use std::process::{Command, Stdio};
-use std::time::{SystemTime, UNIX_EPOCH};
 #[cfg(windows)]
-use winapi::um::{sysinfoapi, wininet, winnt};
+use winapi::um::{sysinfoapi, winnt};
 
+use super::calendar;
+#[cfg(unix)]
+use super::rtc;
 use crate::*;
 
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
 pub(crate) fn get_timezone() -> Result<qapi::GuestTimezone> {
-    let local = Local.timestamp(0, 0);
+    // Anchor on the actual current time rather than the epoch: the UTC offset for a
+    // given zone can differ at the epoch vs. now under historical DST rules.
+    let local = Local::now();
     let zone = Some(local.format("%Z").to_string());
     let offset = local.offset().fix().local_minus_utc() as i64;
 
     Ok(qapi::GuestTimezone { zone, offset })
 }
 
-pub(crate) fn get_time() -> Result<i64> {
-    match SystemTime::now().duration_since(UNIX_EPOCH) {
-        Ok(n) => Ok(n.as_secs() as i64),
-        Err(_) => err!("SystemTime before UNIX EPOCH!"),
+// Reads `clk_id` as (seconds, nanoseconds) through the Y2038-safe `__clock_gettime64`
+// if the running glibc exports it (`None` if it does not, or if the call fails), so
+// 32-bit targets keep working past 2038 even though `libc::timespec::tv_sec` is still
+// a 32-bit `time_t` there.
+#[cfg(all(unix, target_env = "gnu"))]
+fn clock_gettime64(clk_id: libc::clockid_t) -> Option<(i64, i64)> {
+    #[repr(C)]
+    struct Timespec64 {
+        tv_sec: i64,
+        tv_nsec: i64,
+    }
+
+    type ClockGettime64 = unsafe extern "C" fn(libc::clockid_t, *mut Timespec64) -> libc::c_int;
+
+    static SYM: std::sync::OnceLock<Option<ClockGettime64>> = std::sync::OnceLock::new();
+
+    let f = *SYM.get_or_init(|| unsafe {
+        let name = std::ffi::CString::new("__clock_gettime64").unwrap();
+        let sym = libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr());
+        if sym.is_null() {
+            None
+        } else {
+            Some(std::mem::transmute::<*mut libc::c_void, ClockGettime64>(sym))
+        }
+    });
+
+    let f = f?;
+    let mut ts = Timespec64 {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { f(clk_id, &mut ts) } != 0 {
+        None
+    } else {
+        Some((ts.tv_sec, ts.tv_nsec))
     }
 }
 
 #[cfg(unix)]
-pub(crate) fn set_time(time_ns: Option<i64>) -> Result<()> {
-    const NANOS_PER_SEC: i64 = 1_000_000_000;
+fn clock_gettime_ns(clk_id: libc::clockid_t) -> Result<i64> {
+    #[cfg(target_env = "gnu")]
+    if let Some((sec, nsec)) = clock_gettime64(clk_id) {
+        return Ok(sec * NANOS_PER_SEC + nsec);
+    }
 
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    if unsafe { libc::clock_gettime(clk_id, &mut ts) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(ts.tv_sec as i64 * NANOS_PER_SEC + ts.tv_nsec as i64)
+}
+
+#[cfg(unix)]
+pub(crate) fn get_time() -> Result<i64> {
+    clock_gettime_ns(libc::CLOCK_REALTIME)
+}
+
+#[cfg(windows)]
+pub(crate) fn get_time() -> Result<i64> {
+    let mut ft: winapi::shared::minwindef::FILETIME = unsafe { std::mem::zeroed() };
+    unsafe { sysinfoapi::GetSystemTimePreciseAsFileTime(&mut ft) };
+
+    let intervals =
+        ((ft.dwHighDateTime as u64) << 32 | ft.dwLowDateTime as u64) as i64 - win32::INTERVALS_TO_UNIX_EPOCH as i64;
+    Ok(intervals * 100)
+}
+
+// Falls back to the `hwclock` binary when the direct RTC path isn't available:
+// `ENOTTY` when the device doesn't support the ioctls, `EACCES`/`EPERM` without the
+// right permissions (the latter is what Docker's default device-cgroup ACL reports),
+// or `ENOENT` when `/dev/rtc`(0) doesn't exist at all, as in stripped-down containers.
+#[cfg(unix)]
+fn rtc_ioctl_unavailable(err: &std::io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::ENOTTY) | Some(libc::EACCES) | Some(libc::EPERM) | Some(libc::ENOENT)
+    )
+}
+
+#[cfg(unix)]
+fn hwclock_fallback(arg: &str) -> Result<()> {
     if nix::unistd::access("/sbin/hwclock", nix::unistd::AccessFlags::X_OK).is_err() {
         return err!("Can't execute hwclock");
     }
 
     let mut hwclock = Command::new("/sbin/hwclock");
+    hwclock.arg(arg);
     hwclock.stdin(Stdio::null());
     hwclock.stdout(Stdio::null());
     hwclock.stderr(Stdio::null());
 
+    let status = hwclock.status()?;
+    if !status.success() {
+        return err!(format!("hwclock {} failed", arg));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub(crate) fn set_time(time_ns: Option<i64>) -> Result<()> {
     // if user has passed a time to set and the system time is set, we just need
     // to synchronize the hardware clock. However, if no time was passed, user
     // is requesting the opposite: set the system time from the hardware clock
@@ -46,8 +132,8 @@ pub(crate) fn set_time(time_ns: Option<i64>) -> Result<()> {
             return err!(format!("Time {} is too large", time_ns));
         }
         // a bit unsure about the need of such check
-        let dt = chrono::Utc.timestamp(time_ns / NANOS_PER_SEC, (time_ns % NANOS_PER_SEC) as u32);
-        if dt.year() < 1970 || dt.year() >= 2070 {
+        let tm = calendar::time_to_tm(time_ns / NANOS_PER_SEC);
+        if tm.year < 1970 || tm.year >= 2070 {
             return err!("Invalid time");
         }
         let ret =
@@ -55,46 +141,42 @@ pub(crate) fn set_time(time_ns: Option<i64>) -> Result<()> {
         if ret == -1 {
             return Err(std::io::Error::last_os_error().into());
         }
-        hwclock.arg("-w");
-    } else {
-        hwclock.arg("-s");
-    }
 
-    let status = hwclock.status()?;
-    if !status.success() {
-        return err!("hwclock failed to set hardware clock to system time");
+        if let Err(e) = rtc::set_rtc_from_system(time_ns / NANOS_PER_SEC) {
+            if !rtc_ioctl_unavailable(&e) {
+                return Err(e.into());
+            }
+            hwclock_fallback("-w")?;
+        }
+    } else if let Err(e) = rtc::set_system_from_rtc() {
+        if !rtc_ioctl_unavailable(&e) {
+            return Err(e.into());
+        }
+        hwclock_fallback("-s")?;
     }
+
     Ok(())
 }
 
 #[cfg(windows)]
 pub(crate) fn set_time(time_ns: Option<i64>) -> Result<()> {
-    match time_ns {
-        Some(time_ns) => {
-            let st = win32::unix_time_to_system_time(time_ns as u64)?;
-            win32::acquire_privilege(winnt::SE_SYSTEMTIME_NAME)?;
+    // Windows exposes no supported user-mode API to read the hardware RTC directly:
+    // `NtQuerySystemInformation(SystemTimeOfDayInformation)` (and every other candidate)
+    // just returns the same soft clock `GetSystemTime` already does, so there is nothing
+    // authoritative to resync from here without reintroducing an external dependency
+    // (the very `w32tm` subprocess this function replaced). Rather than pretend to
+    // resync by writing the clock's own current value back to itself, report this
+    // explicitly as unsupported; callers wanting a guest-driven resync should use
+    // `guest-set-time-ntp` instead.
+    let time_ns = match time_ns {
+        Some(time_ns) => time_ns,
+        None => return err!("Resyncing the clock with no argument is not supported on Windows"),
+    };
 
-            if unsafe { sysinfoapi::SetSystemTime(&st) } == 0 {
-                return err!("Failed to set system time");
-            }
-        }
-        None => {
-            // Unfortunately, Windows libraries don't provide an easy way to access
-            // RTC yet: https://msdn.microsoft.com/en-us/library/aa908981.aspx
-            //
-            // Instead, a workaround is to use the Windows win32tm command to
-            // resync the time using the Windows Time service.
-            let status = Command::new("w32tm /resync /nowait").status()?;
-            if !status.success() {
-                return err!("w32tm failed");
-            }
-            if let Some(code) = status.code() {
-                let mut flags = 0;
-                if unsafe { wininet::InternetGetConnectedState(&mut flags, 0) } == 0 {
-                    return err!("No internet connection on guest, sync not accurate");
-                }
-            }
-        }
+    let st = win32::unix_time_to_system_time(time_ns as u64)?;
+    let _privilege = win32::acquire_privilege(winnt::SE_SYSTEMTIME_NAME)?;
+    if unsafe { sysinfoapi::SetSystemTime(&st) } == 0 {
+        return err!("Failed to set system time");
     }
 
     Ok(())
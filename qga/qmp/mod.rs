@@ -76,9 +76,27 @@ extern "C" fn qmp_guest_set_vcpus(
     qmp!(vcpus::set(vcpus), errp, -1)
 }
 
+mod calendar;
+#[cfg(unix)]
+mod rtc;
 mod datetime;
 
 #[no_mangle]
 extern "C" fn qmp_guest_get_timezone(errp: *mut *mut sys::Error) -> *mut qapi_sys::GuestTimezone {
     qmp!(datetime::get_timezone(), errp)
 }
+
+#[no_mangle]
+extern "C" fn qmp_guest_get_time(errp: *mut *mut sys::Error) -> libc::c_longlong {
+    qmp!(datetime::get_time(), errp, -1)
+}
+
+mod ntp;
+
+#[no_mangle]
+extern "C" fn qmp_guest_set_time_ntp(server: *const libc::c_char, errp: *mut *mut sys::Error) {
+    let server = unsafe { std::ffi::CStr::from_ptr(server) }
+        .to_string_lossy()
+        .into_owned();
+    qmp!(ntp::sync(&server), errp, ())
+}
@@ -0,0 +1,78 @@
+use std::convert::TryInto;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use super::datetime;
+use crate::*;
+
+const NTP_PORT: u16 = 123;
+const NTP_PACKET_SIZE: usize = 48;
+const NTP_TO_UNIX_EPOCH: i64 = 2_208_988_800;
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+// Anything beyond this is almost certainly a congested link or a bogus reply,
+// not measurement noise.
+const MAX_ROUND_TRIP_NS: i64 = 2 * NANOS_PER_SEC;
+
+fn ntp_to_unix_ns(seconds: u32, fraction: u32) -> i64 {
+    let secs = seconds as i64 - NTP_TO_UNIX_EPOCH;
+    let nanos = fraction as f64 * 1e9 / 2f64.powi(32);
+    secs * NANOS_PER_SEC + nanos as i64
+}
+
+/// Synchronizes the guest clock from `server` using a minimal SNTP client (RFC 4330).
+pub(crate) fn sync(server: &str) -> Result<()> {
+    let addr = match (server, NTP_PORT).to_socket_addrs()?.next() {
+        Some(addr) => addr,
+        None => return err!(format!("Failed to resolve NTP server {}", server)),
+    };
+
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.set_read_timeout(Some(RECV_TIMEOUT))?;
+    socket.connect(addr)?;
+
+    // leap = 0, version = 3, mode = 3 (client); the rest of the request is unused.
+    let mut request = [0u8; NTP_PACKET_SIZE];
+    request[0] = 0x1b;
+
+    let t1 = datetime::get_time()?;
+    socket.send(&request)?;
+
+    let mut response = [0u8; NTP_PACKET_SIZE];
+    let len = socket.recv(&mut response)?;
+    let t4 = datetime::get_time()?;
+    if len < NTP_PACKET_SIZE {
+        return err!(format!(
+            "NTP server {} sent a truncated response ({} of {} bytes)",
+            server, len, NTP_PACKET_SIZE
+        ));
+    }
+
+    let t2 = ntp_to_unix_ns(
+        u32::from_be_bytes(response[32..36].try_into().unwrap()),
+        u32::from_be_bytes(response[36..40].try_into().unwrap()),
+    );
+    let transmit_secs = u32::from_be_bytes(response[40..44].try_into().unwrap());
+    let transmit_frac = u32::from_be_bytes(response[44..48].try_into().unwrap());
+    if transmit_secs == 0 && transmit_frac == 0 {
+        return err!(format!(
+            "NTP server {} sent a kiss-of-death (unsynchronized) response",
+            server
+        ));
+    }
+    let t3 = ntp_to_unix_ns(transmit_secs, transmit_frac);
+
+    let delay = (t4 - t1) - (t3 - t2);
+    if delay.abs() > MAX_ROUND_TRIP_NS {
+        return err!(format!(
+            "NTP round-trip delay to {} is implausibly large ({} ms), refusing to sync",
+            server,
+            delay / 1_000_000
+        ));
+    }
+
+    let offset = ((t2 - t1) + (t3 - t4)) / 2;
+    // Re-read the clock right before applying the offset rather than reusing `t1`, so the
+    // time we set isn't stale by roughly the round-trip delay.
+    datetime::set_time(Some(datetime::get_time()? + offset))
+}
@@ -0,0 +1,98 @@
+use std::fs::{File, OpenOptions};
+use std::os::unix::io::AsRawFd;
+
+use super::calendar;
+use crate::*;
+
+// struct rtc_time, as defined by linux/rtc.h.
+#[repr(C)]
+#[derive(Default)]
+struct RtcTime {
+    tm_sec: i32,
+    tm_min: i32,
+    tm_hour: i32,
+    tm_mday: i32,
+    tm_mon: i32,
+    tm_year: i32,
+    tm_wday: i32,
+    tm_yday: i32,
+    tm_isdst: i32,
+}
+
+const RTC_IOCTL_MAGIC: u64 = b'p' as u64;
+const IOC_READ: u64 = 2;
+const IOC_WRITE: u64 = 1;
+
+const fn ioc(dir: u64, nr: u64, size: usize) -> libc::c_ulong {
+    ((dir << 30) | ((size as u64) << 16) | (RTC_IOCTL_MAGIC << 8) | nr) as libc::c_ulong
+}
+
+// RTC_RD_TIME / RTC_SET_TIME, as defined by linux/rtc.h.
+fn rtc_rd_time() -> libc::c_ulong {
+    ioc(IOC_READ, 0x09, std::mem::size_of::<RtcTime>())
+}
+
+fn rtc_set_time() -> libc::c_ulong {
+    ioc(IOC_WRITE, 0x0a, std::mem::size_of::<RtcTime>())
+}
+
+fn open_rtc() -> std::io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/rtc")
+        .or_else(|_| OpenOptions::new().read(true).write(true).open("/dev/rtc0"))
+}
+
+fn tm_to_rtc_time(tm: &calendar::Tm) -> RtcTime {
+    RtcTime {
+        tm_sec: tm.sec as i32,
+        tm_min: tm.min as i32,
+        tm_hour: tm.hour as i32,
+        tm_mday: tm.day as i32,
+        tm_mon: (tm.month - 1) as i32,
+        tm_year: (tm.year - 1900) as i32,
+        ..Default::default()
+    }
+}
+
+fn rtc_time_to_tm(rtc: &RtcTime) -> calendar::Tm {
+    calendar::Tm {
+        year: rtc.tm_year as i64 + 1900,
+        month: rtc.tm_mon as i64 + 1,
+        day: rtc.tm_mday as i64,
+        hour: rtc.tm_hour as i64,
+        min: rtc.tm_min as i64,
+        sec: rtc.tm_sec as i64,
+    }
+}
+
+/// Sets the system clock from the hardware RTC, via the `RTC_RD_TIME` ioctl.
+pub(crate) fn set_system_from_rtc() -> std::io::Result<()> {
+    let rtc = open_rtc()?;
+    let mut time = RtcTime::default();
+    if unsafe { libc::ioctl(rtc.as_raw_fd(), rtc_rd_time(), &mut time) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let ts = libc::timespec {
+        tv_sec: calendar::tm_to_time(&rtc_time_to_tm(&time)) as libc::time_t,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_settime(libc::CLOCK_REALTIME, &ts) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Writes `now` (Unix seconds) into the hardware RTC, via the `RTC_SET_TIME` ioctl.
+pub(crate) fn set_rtc_from_system(now: i64) -> std::io::Result<()> {
+    let rtc = open_rtc()?;
+    let time = tm_to_rtc_time(&calendar::time_to_tm(now));
+    if unsafe { libc::ioctl(rtc.as_raw_fd(), rtc_set_time(), &time) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
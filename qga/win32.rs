@@ -11,9 +11,47 @@ use crate::*;
 
 const NANOS_PER_SEC: u64 = 1_000_000_000;
 const INTERVALS_PER_SEC: u64 = NANOS_PER_SEC / 100;
-const INTERVALS_TO_UNIX_EPOCH: u64 = 11_644_473_600 * INTERVALS_PER_SEC;
+pub(crate) const INTERVALS_TO_UNIX_EPOCH: u64 = 11_644_473_600 * INTERVALS_PER_SEC;
 
-pub(crate) fn acquire_privilege(name: &str) -> Result<()> {
+/// Holds an enabled token privilege for as long as it is alive, disabling it again on drop
+/// (including on early-return error paths) so callers never leak an elevated privilege.
+pub(crate) struct PrivilegeGuard {
+    token: winnt::HANDLE,
+    luid: winnt::LUID,
+}
+
+impl Drop for PrivilegeGuard {
+    fn drop(&mut self) {
+        let _ = release_privilege(self.token, self.luid);
+        unsafe { CloseHandle(self.token) };
+    }
+}
+
+fn release_privilege(token: winnt::HANDLE, luid: winnt::LUID) -> Result<()> {
+    let mut tp: winnt::TOKEN_PRIVILEGES = unsafe { std::mem::zeroed() };
+    tp.PrivilegeCount = 1;
+    tp.Privileges[0].Luid = luid;
+    tp.Privileges[0].Attributes = 0;
+
+    let size = std::mem::size_of::<winnt::TOKEN_PRIVILEGES>() as u32;
+    let status = unsafe {
+        AdjustTokenPrivileges(
+            token,
+            0,
+            &mut tp,
+            size,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if status == 0 {
+        return err!("unable to release requested privilege");
+    }
+
+    Ok(())
+}
+
+pub(crate) fn acquire_privilege(name: &str) -> Result<PrivilegeGuard> {
     let mut token = std::ptr::null_mut();
     let status = unsafe {
         OpenProcessToken(
@@ -42,6 +80,7 @@ pub(crate) fn acquire_privilege(name: &str) -> Result<()> {
         return err!("no luid for requested privilege");
     }
 
+    let luid = tp.Privileges[0].Luid;
     tp.PrivilegeCount = 1;
     tp.Privileges[0].Attributes = winnt::SE_PRIVILEGE_ENABLED;
     let size = std::mem::size_of::<winnt::TOKEN_PRIVILEGES>() as u32;
@@ -55,12 +94,12 @@ pub(crate) fn acquire_privilege(name: &str) -> Result<()> {
             std::ptr::null_mut(),
         )
     };
-    unsafe { CloseHandle(token) };
     if status == 0 {
+        unsafe { CloseHandle(token) };
         return err!("unable to acquire requested privilege");
     }
 
-    Ok(())
+    Ok(PrivilegeGuard { token, luid })
 }
 
 pub(crate) fn unix_time_to_file_time(time_ns: u64) -> Result<FILETIME> {